@@ -0,0 +1,97 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::io::Read;
+
+/// Expands each of `patterns` as a shell-style glob, returning the matched file paths in the
+/// order the patterns were given (and in glob's own sorted order within a pattern). A pattern of
+/// `-` (stdin) is passed through unchanged rather than globbed. Errors if a non-stdin pattern
+/// matches nothing, since that almost always means a typo in the path.
+pub fn expand_inputs(patterns: &[String]) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut expanded = Vec::new();
+
+    for pattern in patterns {
+        if pattern == "-" {
+            expanded.push(pattern.clone());
+            continue;
+        }
+
+        let mut matched = false;
+        for entry in glob::glob(pattern)? {
+            expanded.push(entry?.to_string_lossy().into_owned());
+            matched = true;
+        }
+
+        if !matched {
+            return Err(format!("no files matched input pattern: {}", pattern).into());
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// Opens each of `paths` (or stdin for `-`), in the order given, without joining them into a
+/// single stream.
+pub fn open_sources(paths: &[String]) -> Result<Vec<Box<dyn Read>>, Box<dyn Error>> {
+    paths
+        .iter()
+        .map(|path| -> Result<Box<dyn Read>, Box<dyn Error>> {
+            if path == "-" {
+                Ok(Box::new(std::io::stdin()))
+            } else {
+                Ok(Box::new(std::fs::File::open(path)?))
+            }
+        })
+        .collect()
+}
+
+/// Opens each of `paths` (or stdin for `-`) and returns a [`Chain`] that reads them as a single
+/// stream, in order.
+pub fn open_chained(paths: &[String]) -> Result<Chain, Box<dyn Error>> {
+    Ok(Chain::new(open_sources(paths)?))
+}
+
+/// A [`Read`] adapter that drains a list of sources in order, advancing to the next one once the
+/// current source returns 0 bytes, so multi-file input looks like a single stream to the parsers.
+pub struct Chain {
+    sources: VecDeque<Box<dyn Read>>,
+}
+
+impl Chain {
+    pub fn new(sources: Vec<Box<dyn Read>>) -> Self {
+        Chain {
+            sources: sources.into(),
+        }
+    }
+}
+
+impl Read for Chain {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while let Some(source) = self.sources.front_mut() {
+            let n = source.read(buf)?;
+            if n == 0 {
+                self.sources.pop_front();
+                continue;
+            }
+            return Ok(n);
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn chain_concatenates_sources_in_order() {
+        let sources: Vec<Box<dyn Read>> = vec![
+            Box::new(Cursor::new(b"abc".to_vec())),
+            Box::new(Cursor::new(b"def".to_vec())),
+        ];
+        let mut chain = Chain::new(sources);
+        let mut out = String::new();
+        chain.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "abcdef");
+    }
+}
@@ -1,25 +1,32 @@
 use std::error::Error;
-use std::io::BufReader;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
 use clap::Parser;
-use env_logger::Env;
+use env_logger::{Env, Target};
 use log::debug;
 
+mod input;
 mod processing;
 mod types;
 
 use log::info;
+use input::{expand_inputs, open_chained, open_sources};
 use processing::{
-    compute_distances, read_and_parse_fasta, read_and_parse_tabular, write_distances_to_file,
-    OutputFormat, OutputMode,
+    compute_and_stream_edge_list, compute_distances, compute_distances_incremental,
+    read_and_parse_fasta, read_and_parse_tabular, write_distances_to_file, ComputeOptions,
+    DuplicatePolicy, OutputFormat, OutputMode,
 };
 use types::InputFormat;
 
 /// This struct represents the command-line arguments
 #[derive(Parser, Debug)]
 struct Cli {
-    input: String,
+    /// One or more input paths; shell-style globs (e.g. `batch_*/*.fasta`) are expanded and all
+    /// matches are concatenated into one dataset. Use `-` for stdin.
+    #[arg(required = true, num_args = 1..)]
+    input: Vec<String>,
     output: String,
     #[arg(value_enum, short = 'i', long, default_value = "fasta")]
     input_format: InputFormat,
@@ -42,25 +49,125 @@ struct Cli {
     #[arg(short = 's', long)]
     skip_header: bool,
 
+    /// Reuse distances from a previous run instead of recomputing the full matrix. Unchanged
+    /// samples are detected via a `<update path>.manifest` sidecar file; the manifest for this
+    /// run's dataset is then written as `<output>.manifest` (or back to `<update path>.manifest`
+    /// when the output is stdout).
+    #[arg(long)]
+    update: Option<String>,
+
+    /// Number of threads to use for the pairwise distance computation (defaults to rayon's pool size)
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+
+    /// Write a TSV joblog (one row per parallel chunk: thread, pair range, elapsed time) to PATH
+    #[arg(long)]
+    joblog: Option<String>,
+
+    /// Keep the first occurrence of a sample label seen across the (possibly multi-file) input
+    /// and skip later ones, instead of erroring
+    #[arg(long, conflicts_with = "allow_duplicates")]
+    dedup: bool,
+
+    /// Keep the last occurrence of a sample label seen across the (possibly multi-file) input,
+    /// overwriting earlier ones, instead of erroring
+    #[arg(long)]
+    allow_duplicates: bool,
+
+    /// Route progress logging (info!/debug!) to this file instead of stderr, so it doesn't
+    /// interleave with the distance matrix when that's written to stdout
+    #[arg(long)]
+    log_file: Option<String>,
+
+    /// Flush every log record immediately instead of letting the log file buffer, so tailing
+    /// `--log-file` shows live progress even when the process's output is redirected
+    #[arg(long)]
+    no_buffering: bool,
+
     #[arg(short = 'v', long)]
     verbose: bool,
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let opts: Cli = Cli::parse();
-    if opts.verbose {
-        env_logger::Builder::from_env(Env::default().default_filter_or("debug")).init();
-    } else {
-        env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+impl Cli {
+    fn duplicate_policy(&self) -> DuplicatePolicy {
+        if self.dedup {
+            DuplicatePolicy::Dedup
+        } else if self.allow_duplicates {
+            DuplicatePolicy::AllowDuplicates
+        } else {
+            DuplicatePolicy::Error
+        }
+    }
+}
+
+/// Wraps a `--log-file` so it can give `--no-buffering` a real effect. `env_logger` calls
+/// `flush()` after writing every single record regardless of how the target stream is wrapped
+/// (see its `Target::Pipe` handling), so a plain `BufWriter` around the file is flushed to disk on
+/// every log call either way. Here, `flush()` is only forwarded to the inner `BufWriter` when
+/// `no_buffering` is set; otherwise it's swallowed and writes accumulate until the buffer fills,
+/// giving the default mode actual buffering. A clone is kept in `main` to force one real flush
+/// before exit, since `log::set_boxed_logger` leaks the boxed target and never runs its `Drop`.
+#[derive(Clone)]
+struct LogFileWriter {
+    inner: Arc<Mutex<BufWriter<std::fs::File>>>,
+    no_buffering: bool,
+}
+
+impl LogFileWriter {
+    fn new(file: std::fs::File, no_buffering: bool) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(BufWriter::new(file))),
+            no_buffering,
+        }
     }
 
-    let reader: Box<dyn std::io::Read> = if opts.input == "-" {
-        Box::new(std::io::stdin())
+    fn flush_now(&self) -> io::Result<()> {
+        self.inner.lock().unwrap().flush()
+    }
+}
+
+impl Write for LogFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.no_buffering {
+            self.inner.lock().unwrap().flush()
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Opens `output` for writing (truncating it if it already exists), or stdout if `output` is `-`.
+fn open_writer(output: &str) -> Result<BufWriter<Box<dyn std::io::Write + Send>>, Box<dyn Error>> {
+    let writer: Box<dyn std::io::Write + Send> = if output == "-" {
+        Box::new(std::io::stdout())
     } else {
-        Box::new(std::fs::File::open(&opts.input)?)
+        Box::new(std::fs::File::create(output)?)
     };
+    Ok(BufWriter::new(writer))
+}
 
-    let reader = BufReader::new(reader);
+fn main() -> Result<(), Box<dyn Error>> {
+    let opts: Cli = Cli::parse();
+    let default_filter = if opts.verbose { "debug" } else { "info" };
+    let mut logger_builder =
+        env_logger::Builder::from_env(Env::default().default_filter_or(default_filter));
+    let mut log_file_writer = None;
+    if let Some(log_file) = &opts.log_file {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_file)?;
+        let writer = LogFileWriter::new(file, opts.no_buffering);
+        log_file_writer = Some(writer.clone());
+        logger_builder.target(Target::Pipe(Box::new(writer)));
+    }
+    logger_builder.init();
+
+    let input_paths = expand_inputs(&opts.input)?;
 
     // print version info
     info!("Version: {}", env!("CARGO_PKG_VERSION"));
@@ -69,35 +176,99 @@ fn main() -> Result<(), Box<dyn Error>> {
     debug!("Cli options: {:?}", opts);
 
     let start = Instant::now();
+    let duplicate_policy = opts.duplicate_policy();
     let data_map = match opts.input_format {
         InputFormat::Fasta | InputFormat::FastaAll => {
-            read_and_parse_fasta(reader, opts.input_format)?
+            let reader = BufReader::new(open_chained(&input_paths)?);
+            read_and_parse_fasta(reader, opts.input_format, duplicate_policy)?
+        }
+        _ => {
+            let readers = open_sources(&input_paths)?
+                .into_iter()
+                .map(|source| Box::new(BufReader::new(source)) as Box<dyn BufRead>)
+                .collect();
+            read_and_parse_tabular(
+                readers,
+                opts.input_format,
+                opts.input_sep,
+                opts.skip_header,
+                duplicate_policy,
+            )?
         }
-        _ => read_and_parse_tabular(reader, opts.input_format, opts.input_sep, opts.skip_header)?,
     };
     debug!("Reading time: {:?}", start.elapsed());
     let start = Instant::now();
 
     info!("Computing distances and writing to file: {}", &opts.output);
 
-    // Compute the pairwise distances
-    let distances = compute_distances(&data_map, opts.maxdist, opts.output_mode);
+    let joblog_path = opts.joblog.as_ref().map(std::path::Path::new);
 
-    let writer: Box<dyn std::io::Write> = if opts.output == "-" {
-        Box::new(std::io::stdout())
+    // A fresh edge-list run streams qualifying pairs straight to the output file as they're
+    // computed, without ever materializing the full `Vec<Distance>`. `--update` still needs the
+    // full result in hand to reuse cached distances, so it goes through the materialized path
+    // below. Either way, `opts.output` is only opened (truncating it) once its own computation no
+    // longer needs to read anything from disk — `--update <path>` commonly reruns against the same
+    // path as `--output`, and opening it any earlier would truncate the previous matrix before
+    // `compute_distances_incremental` gets to read it.
+    if opts.update.is_none() && opts.output_format == OutputFormat::EdgeList {
+        let mut writer = open_writer(&opts.output)?;
+        compute_and_stream_edge_list(
+            &data_map,
+            opts.maxdist,
+            opts.output_mode,
+            opts.input_format,
+            opts.threads,
+            joblog_path,
+            &mut writer,
+            opts.output_sep,
+        )?;
     } else {
-        Box::new(std::fs::File::create(&opts.output)?)
-    };
+        // Compute the pairwise distances, reusing a previous matrix when `--update` is given
+        let distances = match &opts.update {
+            Some(prev_matrix) => {
+                // Keyed off `prev_matrix` (the file actually being read), not `opts.output`, so a
+                // chain of runs (`v1.tsv` -> `v2.tsv --update v1.tsv` -> `v3.tsv --update v2.tsv`)
+                // each consult the manifest the previous run in the chain actually wrote.
+                let manifest_path = format!("{}.manifest", prev_matrix);
+                let distances = compute_distances_incremental(
+                    &data_map,
+                    opts.maxdist,
+                    opts.output_mode,
+                    opts.input_format,
+                    std::path::Path::new(prev_matrix),
+                    std::path::Path::new(&manifest_path),
+                    opts.output_sep,
+                    ComputeOptions {
+                        threads: opts.threads,
+                        joblog: joblog_path,
+                    },
+                )?;
+                // The manifest just rewritten describes this run's dataset, so it moves next to
+                // this run's output, where the next run in the chain will look for it.
+                if opts.output != "-" && opts.output != *prev_matrix {
+                    std::fs::rename(&manifest_path, format!("{}.manifest", opts.output))?;
+                }
+                distances
+            }
+            None => compute_distances(
+                &data_map,
+                opts.maxdist,
+                opts.output_mode,
+                opts.input_format,
+                opts.threads,
+                joblog_path,
+            )?,
+        };
 
-    let mut writer = std::io::BufWriter::new(writer);
-
-    write_distances_to_file(
-        distances,
-        &mut writer,
-        opts.output_sep,
-        opts.output_format,
-        data_map.len(),
-    )?;
+        let mut writer = open_writer(&opts.output)?;
+        write_distances_to_file(
+            distances,
+            &mut writer,
+            opts.output_sep,
+            opts.output_format,
+            data_map.len(),
+        )?;
+    }
 
     debug!("Computing + Writing time: {:?}", start.elapsed());
     match opts.maxdist {
@@ -107,5 +278,9 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     info!("Done");
 
+    if let Some(writer) = log_file_writer {
+        writer.flush_now()?;
+    }
+
     Ok(())
 }
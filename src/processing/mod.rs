@@ -0,0 +1,755 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+
+use crate::types::InputFormat;
+
+mod update;
+pub use update::{compute_distances_incremental, ComputeOptions};
+
+/// A sample's normalized profile: one entry per site (FASTA base) or per locus (allele call)
+pub type Profile = Vec<String>;
+
+/// Maps sample label to its normalized profile, as read from the input
+pub type DataMap = HashMap<String, Profile>;
+
+/// Supported output formats for the pairwise distances
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Plain-text matrix, separated by `output_sep`
+    Tabular,
+    /// Sparse neighbor list: only pairs with a distance (i.e. not excluded by `--maxdist`) are
+    /// emitted, as `sample_a`, `sample_b`, `distance` rows. Meant to be combined with `--maxdist`
+    /// to produce a compact graph for single-linkage clustering / network tools, instead of a
+    /// dense matrix that's mostly empty once a threshold is applied.
+    EdgeList,
+}
+
+/// Which pairs of the distance matrix to emit
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Only the lower triangle (i > j), excluding the diagonal
+    LowerTriangle,
+    /// The full symmetric matrix, including the diagonal
+    Full,
+}
+
+/// One computed pairwise distance, or `None` if it exceeded the requested `maxdist`
+pub type Distance = Option<usize>;
+
+/// The pairwise distances for a dataset, in canonical (sorted-label) sample order
+pub struct DistanceResult {
+    pub labels: Vec<String>,
+    pub mode: OutputMode,
+    pub distances: Vec<Distance>,
+}
+
+/// How to handle a sample label appearing more than once across the (possibly concatenated)
+/// input, e.g. when several per-sample files passed on the command line happen to overlap.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Reject the input with an error (default)
+    #[default]
+    Error,
+    /// Keep the first occurrence of a label and silently skip later ones
+    Dedup,
+    /// Keep the last occurrence of a label, overwriting earlier ones
+    AllowDuplicates,
+}
+
+/// Reads a FASTA alignment and builds a [`DataMap`] of sample label to per-site profile.
+///
+/// Sites are kept as-is here regardless of format; [`InputFormat::Fasta`] vs.
+/// [`InputFormat::FastaAll`] only affects how [`pairwise_distance`] scores a pair of sites (core
+/// positions only vs. every position, see that function's doc comment).
+pub fn read_and_parse_fasta(
+    reader: impl BufRead,
+    _input_format: InputFormat,
+    duplicate_policy: DuplicatePolicy,
+) -> Result<DataMap, Box<dyn Error>> {
+    let mut data_map = DataMap::new();
+    let mut current_label: Option<String> = None;
+    let mut current_seq = String::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(label) = line.strip_prefix('>') {
+            if let Some(prev_label) = current_label.take() {
+                insert_sample(&mut data_map, prev_label, to_profile(&current_seq), duplicate_policy)?;
+            }
+            current_label = Some(label.trim().to_string());
+            current_seq.clear();
+        } else {
+            current_seq.push_str(line.trim());
+        }
+    }
+    if let Some(label) = current_label {
+        insert_sample(&mut data_map, label, to_profile(&current_seq), duplicate_policy)?;
+    }
+
+    Ok(data_map)
+}
+
+/// Reads a tabular allele profile (one sample per row, one locus per column) from one or more
+/// sources and builds a [`DataMap`] of sample label to per-locus profile.
+///
+/// Each source is read independently (rather than concatenated into one stream), so
+/// `skip_header` drops the first row of every source, not just the first row overall —
+/// multiple tabular inputs passed on the command line each carry their own header.
+pub fn read_and_parse_tabular(
+    readers: Vec<Box<dyn BufRead>>,
+    _input_format: InputFormat,
+    sep: char,
+    skip_header: bool,
+    duplicate_policy: DuplicatePolicy,
+) -> Result<DataMap, Box<dyn Error>> {
+    let mut data_map = DataMap::new();
+
+    for reader in readers {
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if i == 0 && skip_header {
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut fields = line.split(sep);
+            let label = fields
+                .next()
+                .ok_or("tabular row is missing a sample label")?
+                .to_string();
+            let profile: Profile = fields.map(|field| field.to_string()).collect();
+            insert_sample(&mut data_map, label, profile, duplicate_policy)?;
+        }
+    }
+
+    Ok(data_map)
+}
+
+fn insert_sample(
+    data_map: &mut DataMap,
+    label: String,
+    profile: Profile,
+    duplicate_policy: DuplicatePolicy,
+) -> Result<(), Box<dyn Error>> {
+    if data_map.contains_key(&label) {
+        match duplicate_policy {
+            DuplicatePolicy::Error => {
+                return Err(format!(
+                    "duplicate sample label: {} (use --dedup or --allow-duplicates)",
+                    label
+                )
+                .into())
+            }
+            DuplicatePolicy::Dedup => return Ok(()),
+            DuplicatePolicy::AllowDuplicates => {}
+        }
+    }
+    data_map.insert(label, profile);
+    Ok(())
+}
+
+fn to_profile(seq: &str) -> Profile {
+    seq.chars().map(|c| c.to_string()).collect()
+}
+
+/// Counts the number of differing sites/loci between two profiles, stopping early once the
+/// count exceeds `maxdist` (if given) since the exact value beyond that point is never used.
+///
+/// For [`InputFormat::Fasta`], a site is skipped (treated as neither a match nor a mismatch) if
+/// either profile has a gap (`-`) or ambiguous (`N`) call there, so only core positions shared by
+/// both samples count; [`InputFormat::FastaAll`] and [`InputFormat::Tabular`] compare every
+/// position literally.
+pub(crate) fn pairwise_distance(
+    a: &Profile,
+    b: &Profile,
+    maxdist: Option<usize>,
+    input_format: InputFormat,
+) -> Distance {
+    let mut diff = 0usize;
+    for (x, y) in a.iter().zip(b.iter()) {
+        if input_format == InputFormat::Fasta && (is_gap_or_ambiguous(x) || is_gap_or_ambiguous(y))
+        {
+            continue;
+        }
+        if x != y {
+            diff += 1;
+            if let Some(max) = maxdist {
+                if diff > max {
+                    return None;
+                }
+            }
+        }
+    }
+    Some(diff)
+}
+
+/// Whether a FASTA site is a gap or an ambiguous (N) call, and should be excluded from core-only
+/// ([`InputFormat::Fasta`]) distance comparisons.
+fn is_gap_or_ambiguous(site: &str) -> bool {
+    site == "-" || site.eq_ignore_ascii_case("n")
+}
+
+/// Computes the pairwise distances between every sample in `data_map`.
+///
+/// Samples are ordered canonically (sorted by label) so the resulting matrix is stable across
+/// runs and independent of the hash map's iteration order. The pair index space is split into
+/// contiguous chunks and computed with a rayon thread pool sized by `threads` (defaulting to
+/// rayon's own pool size); each chunk writes into its own slice of a preallocated result buffer
+/// so the output order never depends on scheduling. If `joblog` is given, a TSV row per chunk is
+/// written recording which thread ran it, its pair range, and its wall-clock time.
+pub fn compute_distances(
+    data_map: &DataMap,
+    maxdist: Option<usize>,
+    mode: OutputMode,
+    input_format: InputFormat,
+    threads: Option<usize>,
+    joblog: Option<&Path>,
+) -> Result<DistanceResult, Box<dyn Error>> {
+    let mut labels: Vec<String> = data_map.keys().cloned().collect();
+    labels.sort();
+
+    let n = labels.len();
+    let pairs: Vec<(usize, usize)> = match mode {
+        OutputMode::LowerTriangle => (0..n).flat_map(|i| (0..i).map(move |j| (i, j))).collect(),
+        OutputMode::Full => (0..n).flat_map(|i| (0..n).map(move |j| (i, j))).collect(),
+    };
+
+    let (distances, log) =
+        run_pairs_in_parallel(data_map, &labels, &pairs, maxdist, input_format, threads);
+
+    if let Some(path) = joblog {
+        write_joblog(path, &log)?;
+    }
+
+    Ok(DistanceResult {
+        labels,
+        mode,
+        distances,
+    })
+}
+
+/// One row of the `--joblog` TSV: the work done by a single chunk of pairs.
+struct JobLogRow {
+    chunk_id: usize,
+    thread: usize,
+    pair_start: usize,
+    pair_end: usize,
+    n_pairs: usize,
+    elapsed: Duration,
+}
+
+/// Splits `pairs` into contiguous chunks and computes each chunk's distances on a rayon thread
+/// pool, writing results into a preallocated buffer indexed by pair position.
+fn run_pairs_in_parallel(
+    data_map: &DataMap,
+    labels: &[String],
+    pairs: &[(usize, usize)],
+    maxdist: Option<usize>,
+    input_format: InputFormat,
+    threads: Option<usize>,
+) -> (Vec<Distance>, Vec<JobLogRow>) {
+    let num_chunks = threads.unwrap_or_else(rayon::current_num_threads).max(1);
+    let chunk_size = pairs.len().div_ceil(num_chunks).max(1);
+
+    let mut distances = vec![None; pairs.len()];
+    let log = Mutex::new(Vec::new());
+
+    // `distances.par_chunks_mut` below borrows `distances` mutably on every call, so the closure
+    // is `FnMut` and must be bound `mut` to be callable directly (as the serial `None` branch
+    // does) as well as passed to `ThreadPool::install`.
+    let mut run = || {
+        distances
+            .par_chunks_mut(chunk_size)
+            .zip(pairs.par_chunks(chunk_size))
+            .enumerate()
+            .for_each(|(chunk_id, (dist_chunk, pair_chunk))| {
+                let start = Instant::now();
+                for (slot, &(i, j)) in dist_chunk.iter_mut().zip(pair_chunk.iter()) {
+                    *slot = if i == j {
+                        Some(0)
+                    } else {
+                        pairwise_distance(
+                            &data_map[&labels[i]],
+                            &data_map[&labels[j]],
+                            maxdist,
+                            input_format,
+                        )
+                    };
+                }
+                let pair_start = chunk_id * chunk_size;
+                log.lock().unwrap().push(JobLogRow {
+                    chunk_id,
+                    thread: rayon::current_thread_index().unwrap_or(0),
+                    pair_start,
+                    pair_end: pair_start + pair_chunk.len(),
+                    n_pairs: pair_chunk.len(),
+                    elapsed: start.elapsed(),
+                });
+            });
+    };
+
+    match threads {
+        Some(n) => ThreadPoolBuilder::new()
+            .num_threads(n)
+            .build()
+            .expect("failed to build rayon thread pool")
+            .install(run),
+        None => run(),
+    }
+
+    let mut log = log.into_inner().expect("joblog mutex poisoned");
+    log.sort_by_key(|row| row.chunk_id);
+    (distances, log)
+}
+
+fn write_joblog(path: &Path, log: &[JobLogRow]) -> Result<(), Box<dyn Error>> {
+    let mut out = String::from("chunk_id\tthread\tpair_start\tpair_end\tn_pairs\telapsed_ms\n");
+    for row in log {
+        out.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{:.3}\n",
+            row.chunk_id,
+            row.thread,
+            row.pair_start,
+            row.pair_end,
+            row.n_pairs,
+            row.elapsed.as_secs_f64() * 1000.0
+        ));
+    }
+    fs::write(path, out)?;
+    Ok(())
+}
+
+/// Writes the computed distances to `writer` according to `output_format`.
+pub fn write_distances_to_file(
+    distances: DistanceResult,
+    writer: &mut impl Write,
+    sep: char,
+    output_format: OutputFormat,
+    _n_samples: usize,
+) -> Result<(), Box<dyn Error>> {
+    match output_format {
+        OutputFormat::Tabular => write_tabular(distances, writer, sep),
+        OutputFormat::EdgeList => write_edge_list(distances, writer, sep),
+    }
+}
+
+fn write_tabular(
+    distances: DistanceResult,
+    writer: &mut impl Write,
+    sep: char,
+) -> Result<(), Box<dyn Error>> {
+    let n = distances.labels.len();
+    writeln!(writer, "{}", distances.labels.join(&sep.to_string()))?;
+
+    let width = match distances.mode {
+        OutputMode::LowerTriangle => 0, // each row i has i columns
+        OutputMode::Full => n,
+    };
+
+    let mut idx = 0;
+    for i in 0..n {
+        let row_len = match distances.mode {
+            OutputMode::LowerTriangle => i,
+            OutputMode::Full => width,
+        };
+        let mut row = Vec::with_capacity(row_len + 1);
+        row.push(distances.labels[i].clone());
+        for _ in 0..row_len {
+            let cell = match distances.distances[idx] {
+                Some(d) => d.to_string(),
+                None => String::new(),
+            };
+            row.push(cell);
+            idx += 1;
+        }
+        writeln!(writer, "{}", row.join(&sep.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Filters an already-computed [`DistanceResult`] down to its qualifying pairs as `sample_a`,
+/// `sample_b`, `distance` rows. Self-pairs (distance 0) are skipped.
+///
+/// `distances` here is already fully materialized by the caller, so this does not by itself avoid
+/// the O(n^2) compute/memory cost for a fresh run — that's what [`compute_and_stream_edge_list`]
+/// is for. This function exists for callers (namely `--update`) that already need the full
+/// [`DistanceResult`] in hand for other reasons (reusing cached distances by unordered pair key),
+/// so there's nothing left to stream from by the time output formatting runs.
+fn write_edge_list(
+    distances: DistanceResult,
+    writer: &mut impl Write,
+    sep: char,
+) -> Result<(), Box<dyn Error>> {
+    let n = distances.labels.len();
+    let sep = sep.to_string();
+
+    let mut idx = 0;
+    for i in 0..n {
+        let row_len = match distances.mode {
+            OutputMode::LowerTriangle => i,
+            OutputMode::Full => n,
+        };
+        for j in 0..row_len {
+            if let Some(d) = distances.distances[idx] {
+                // `Full` mode's matrix holds both (i,j) and (j,i); an edge list is undirected, so
+                // only the j < i half is emitted to avoid doubling every edge.
+                let emit = match distances.mode {
+                    OutputMode::LowerTriangle => i != j,
+                    OutputMode::Full => j < i,
+                };
+                if emit {
+                    writeln!(
+                        writer,
+                        "{}{sep}{}{sep}{}",
+                        distances.labels[i], distances.labels[j], d
+                    )?;
+                }
+            }
+            idx += 1;
+        }
+    }
+
+    Ok(())
+}
+
+/// Computes pairwise distances and writes qualifying pairs straight to `writer` as `sample_a`,
+/// `sample_b`, `distance` rows, chunked and parallelized like [`compute_distances`], but without
+/// ever materializing a `Vec<Distance>` for the full pair space — only rows that pass `maxdist`
+/// are ever held in memory, and only for as long as it takes to write them out. Self-pairs are
+/// excluded from the pair space entirely rather than computed and filtered.
+///
+/// An edge list is undirected, so regardless of `mode` only the lower-triangle (j < i) half of
+/// the pair space is computed — unlike the dense tabular matrix, `Full` mode here would otherwise
+/// double every edge by also emitting its (j, i) mirror.
+///
+/// This is the path taken for a fresh (non-`--update`) run with `--output-format edge-list`; see
+/// [`write_edge_list`] for why `--update` still goes through a materialized [`DistanceResult`].
+#[allow(clippy::too_many_arguments)]
+pub fn compute_and_stream_edge_list<W: Write + Send>(
+    data_map: &DataMap,
+    maxdist: Option<usize>,
+    _mode: OutputMode,
+    input_format: InputFormat,
+    threads: Option<usize>,
+    joblog: Option<&Path>,
+    writer: &mut W,
+    sep: char,
+) -> Result<(), Box<dyn Error>> {
+    let mut labels: Vec<String> = data_map.keys().cloned().collect();
+    labels.sort();
+
+    let n = labels.len();
+    let pairs: Vec<(usize, usize)> = (0..n).flat_map(|i| (0..i).map(move |j| (i, j))).collect();
+
+    let num_chunks = threads.unwrap_or_else(rayon::current_num_threads).max(1);
+    let chunk_size = pairs.len().div_ceil(num_chunks).max(1);
+
+    let sep = sep.to_string();
+    let out = Mutex::new(writer);
+    let write_error: Mutex<Option<std::io::Error>> = Mutex::new(None);
+    let log = Mutex::new(Vec::new());
+
+    let run = || {
+        pairs
+            .par_chunks(chunk_size)
+            .enumerate()
+            .for_each(|(chunk_id, pair_chunk)| {
+                let start = Instant::now();
+                let mut rows = String::new();
+                for &(i, j) in pair_chunk {
+                    if let Some(d) = pairwise_distance(
+                        &data_map[&labels[i]],
+                        &data_map[&labels[j]],
+                        maxdist,
+                        input_format,
+                    ) {
+                        rows.push_str(&labels[i]);
+                        rows.push_str(&sep);
+                        rows.push_str(&labels[j]);
+                        rows.push_str(&sep);
+                        rows.push_str(&d.to_string());
+                        rows.push('\n');
+                    }
+                }
+                if !rows.is_empty() {
+                    if let Err(e) = out.lock().unwrap().write_all(rows.as_bytes()) {
+                        *write_error.lock().unwrap() = Some(e);
+                    }
+                }
+                let pair_start = chunk_id * chunk_size;
+                log.lock().unwrap().push(JobLogRow {
+                    chunk_id,
+                    thread: rayon::current_thread_index().unwrap_or(0),
+                    pair_start,
+                    pair_end: pair_start + pair_chunk.len(),
+                    n_pairs: pair_chunk.len(),
+                    elapsed: start.elapsed(),
+                });
+            });
+    };
+
+    match threads {
+        Some(n) => ThreadPoolBuilder::new()
+            .num_threads(n)
+            .build()
+            .expect("failed to build rayon thread pool")
+            .install(run),
+        None => run(),
+    }
+
+    if let Some(e) = write_error
+        .into_inner()
+        .expect("write-error mutex poisoned")
+    {
+        return Err(Box::new(e));
+    }
+
+    if let Some(path) = joblog {
+        let mut log = log.into_inner().expect("joblog mutex poisoned");
+        log.sort_by_key(|row| row.chunk_id);
+        write_joblog(path, &log)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn fasta_core_mode_ignores_gap_and_n_columns() {
+        let a: Profile = vec!["A".into(), "-".into(), "C".into(), "N".into()];
+        let b: Profile = vec!["A".into(), "T".into(), "G".into(), "N".into()];
+
+        assert_eq!(pairwise_distance(&a, &b, None, InputFormat::Fasta), Some(1));
+        assert_eq!(
+            pairwise_distance(&a, &b, None, InputFormat::FastaAll),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn tabular_skip_header_applies_per_source_file() {
+        let source_a: Box<dyn BufRead> =
+            Box::new(Cursor::new(b"label\tl1\tl2\ns1\tA\tT\n".to_vec()));
+        let source_b: Box<dyn BufRead> =
+            Box::new(Cursor::new(b"label\tl1\tl2\ns2\tA\tG\n".to_vec()));
+
+        let data_map = read_and_parse_tabular(
+            vec![source_a, source_b],
+            InputFormat::Tabular,
+            '\t',
+            true,
+            DuplicatePolicy::Error,
+        )
+        .unwrap();
+
+        assert_eq!(data_map.len(), 2);
+        assert!(data_map.contains_key("s1"));
+        assert!(data_map.contains_key("s2"));
+        assert!(!data_map.contains_key("label"));
+    }
+
+    #[test]
+    fn streaming_edge_list_matches_materialized_filter() {
+        let mut data_map = DataMap::new();
+        data_map.insert("s1".to_string(), vec!["A".into(), "A".into()]);
+        data_map.insert("s2".to_string(), vec!["A".into(), "T".into()]);
+        data_map.insert("s3".to_string(), vec!["T".into(), "T".into()]);
+
+        let materialized = compute_distances(
+            &data_map,
+            Some(1),
+            OutputMode::LowerTriangle,
+            InputFormat::Fasta,
+            None,
+            None,
+        )
+        .unwrap();
+        let mut filtered = Vec::new();
+        write_distances_to_file(
+            materialized,
+            &mut filtered,
+            '\t',
+            OutputFormat::EdgeList,
+            data_map.len(),
+        )
+        .unwrap();
+
+        let mut streamed = Vec::new();
+        compute_and_stream_edge_list(
+            &data_map,
+            Some(1),
+            OutputMode::LowerTriangle,
+            InputFormat::Fasta,
+            None,
+            None,
+            &mut streamed,
+            '\t',
+        )
+        .unwrap();
+
+        let mut filtered_lines: Vec<&str> =
+            std::str::from_utf8(&filtered).unwrap().lines().collect();
+        let mut streamed_lines: Vec<&str> =
+            std::str::from_utf8(&streamed).unwrap().lines().collect();
+        filtered_lines.sort();
+        streamed_lines.sort();
+
+        assert_eq!(filtered_lines, streamed_lines);
+    }
+
+    #[test]
+    fn full_mode_edge_list_emits_each_edge_once() {
+        let mut data_map = DataMap::new();
+        data_map.insert("s1".to_string(), vec!["A".into(), "A".into()]);
+        data_map.insert("s2".to_string(), vec!["A".into(), "T".into()]);
+        data_map.insert("s3".to_string(), vec!["T".into(), "T".into()]);
+
+        let materialized = compute_distances(
+            &data_map,
+            None,
+            OutputMode::Full,
+            InputFormat::Fasta,
+            None,
+            None,
+        )
+        .unwrap();
+        let mut edges = Vec::new();
+        write_distances_to_file(
+            materialized,
+            &mut edges,
+            '\t',
+            OutputFormat::EdgeList,
+            data_map.len(),
+        )
+        .unwrap();
+
+        let lines: Vec<&str> = std::str::from_utf8(&edges).unwrap().lines().collect();
+        // 3 samples -> 3 undirected pairs; a doubled matrix (both (i,j) and (j,i)) would yield 6.
+        assert_eq!(lines.len(), 3);
+
+        let mut streamed = Vec::new();
+        compute_and_stream_edge_list(
+            &data_map,
+            None,
+            OutputMode::Full,
+            InputFormat::Fasta,
+            None,
+            None,
+            &mut streamed,
+            '\t',
+        )
+        .unwrap();
+        let streamed_lines: Vec<&str> = std::str::from_utf8(&streamed).unwrap().lines().collect();
+        assert_eq!(streamed_lines.len(), 3);
+    }
+
+    #[test]
+    fn parallel_distances_match_across_thread_counts() {
+        let mut data_map = DataMap::new();
+        data_map.insert("s1".to_string(), vec!["A".into(), "A".into(), "A".into()]);
+        data_map.insert("s2".to_string(), vec!["A".into(), "T".into(), "A".into()]);
+        data_map.insert("s3".to_string(), vec!["T".into(), "T".into(), "A".into()]);
+        data_map.insert("s4".to_string(), vec!["T".into(), "T".into(), "T".into()]);
+
+        let single_threaded = compute_distances(
+            &data_map,
+            None,
+            OutputMode::LowerTriangle,
+            InputFormat::Fasta,
+            Some(1),
+            None,
+        )
+        .unwrap();
+        let multi_threaded = compute_distances(
+            &data_map,
+            None,
+            OutputMode::LowerTriangle,
+            InputFormat::Fasta,
+            Some(4),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(single_threaded.labels, multi_threaded.labels);
+        assert_eq!(single_threaded.distances, multi_threaded.distances);
+    }
+
+    #[test]
+    fn duplicate_label_errors_by_default() {
+        let mut data_map = DataMap::new();
+        insert_sample(
+            &mut data_map,
+            "s1".to_string(),
+            vec!["A".into()],
+            DuplicatePolicy::Error,
+        )
+        .unwrap();
+
+        let err = insert_sample(
+            &mut data_map,
+            "s1".to_string(),
+            vec!["T".into()],
+            DuplicatePolicy::Error,
+        );
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn duplicate_label_dedup_keeps_first_occurrence() {
+        let mut data_map = DataMap::new();
+        insert_sample(
+            &mut data_map,
+            "s1".to_string(),
+            vec!["A".into()],
+            DuplicatePolicy::Dedup,
+        )
+        .unwrap();
+        insert_sample(
+            &mut data_map,
+            "s1".to_string(),
+            vec!["T".into()],
+            DuplicatePolicy::Dedup,
+        )
+        .unwrap();
+
+        assert_eq!(data_map.len(), 1);
+        assert_eq!(data_map["s1"], vec!["A".to_string()]);
+    }
+
+    #[test]
+    fn duplicate_label_allow_duplicates_keeps_last_occurrence() {
+        let mut data_map = DataMap::new();
+        insert_sample(
+            &mut data_map,
+            "s1".to_string(),
+            vec!["A".into()],
+            DuplicatePolicy::AllowDuplicates,
+        )
+        .unwrap();
+        insert_sample(
+            &mut data_map,
+            "s1".to_string(),
+            vec!["T".into()],
+            DuplicatePolicy::AllowDuplicates,
+        )
+        .unwrap();
+
+        assert_eq!(data_map.len(), 1);
+        assert_eq!(data_map["s1"], vec!["T".to_string()]);
+    }
+}
@@ -0,0 +1,456 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+use log::info;
+
+use super::{
+    compute_distances, run_pairs_in_parallel, write_joblog, DataMap, Distance, DistanceResult,
+    OutputMode,
+};
+use crate::types::InputFormat;
+
+/// Options forwarded from the CLI that affect how a fallback full recompute is parallelized
+/// and logged, kept together so [`compute_distances_incremental`] doesn't grow an unwieldy
+/// parameter list.
+pub struct ComputeOptions<'a> {
+    pub threads: Option<usize>,
+    pub joblog: Option<&'a Path>,
+}
+
+/// Manifest format version, bumped whenever the hashing algorithm changes so stale manifests
+/// are detected and discarded rather than silently producing wrong cached distances.
+const MANIFEST_VERSION: &str = "distle-manifest-v1";
+
+/// The sidecar manifest written by a previous run: per-sample content hashes plus the `maxdist`
+/// that run was computed with. The latter matters because a cached distance that was capped (or
+/// left uncapped) at one threshold isn't valid at a different one.
+struct Manifest {
+    hashes: HashMap<String, String>,
+    maxdist: Option<usize>,
+}
+
+/// A previous run's distances, keyed by unordered label pair (see [`pair_key`]), as parsed by
+/// [`load_previous_matrix`].
+type PairDistanceCache = HashMap<(String, String), Distance>;
+
+/// Computes pairwise distances for `data_map`, reusing cached distances from `prev_matrix_path`
+/// for any sample pair whose members are both unchanged since the run that produced it.
+///
+/// A sample is considered unchanged when its current content hash matches the one recorded for
+/// it in `manifest_path`. Falls back to a full recompute (mirroring [`compute_distances`]) when
+/// the manifest is missing, the previous matrix is missing, the manifest's hash algorithm version
+/// doesn't match this build's, or `maxdist` differs from the one the manifest records — otherwise
+/// a distance capped (or left uncapped) under a different threshold would be reused as-is. In
+/// every case, `manifest_path` is rewritten afterwards with hashes for the current dataset so the
+/// next run can build on this one.
+#[allow(clippy::too_many_arguments)]
+pub fn compute_distances_incremental(
+    data_map: &DataMap,
+    maxdist: Option<usize>,
+    mode: OutputMode,
+    input_format: InputFormat,
+    prev_matrix_path: &Path,
+    manifest_path: &Path,
+    sep: char,
+    compute_options: ComputeOptions,
+) -> Result<DistanceResult, Box<dyn Error>> {
+    let current_hashes: HashMap<String, String> = data_map
+        .iter()
+        .map(|(label, profile)| (label.clone(), hash_profile(profile)))
+        .collect();
+
+    let cached = read_manifest(manifest_path)?
+        .filter(|manifest| manifest.maxdist == maxdist)
+        .and_then(|manifest| {
+            load_previous_matrix(prev_matrix_path, sep, mode)
+                .ok()
+                .flatten()
+                .map(|prev_cache| (manifest.hashes, prev_cache))
+        });
+
+    let result = match cached {
+        Some((prev_hashes, prev_cache)) => {
+            let mut labels: Vec<String> = data_map.keys().cloned().collect();
+            labels.sort();
+
+            let unchanged: Vec<bool> = labels
+                .iter()
+                .map(|label| prev_hashes.get(label) == Some(&current_hashes[label]))
+                .collect();
+
+            let n = labels.len();
+            let pairs: Vec<(usize, usize)> = match mode {
+                OutputMode::LowerTriangle => {
+                    (0..n).flat_map(|i| (0..i).map(move |j| (i, j))).collect()
+                }
+                OutputMode::Full => (0..n).flat_map(|i| (0..n).map(move |j| (i, j))).collect(),
+            };
+
+            // Reuse what we can directly; collect the rest so it can be recomputed in parallel
+            // below instead of falling back to a sequential loop, per `--threads`/`--joblog`.
+            let mut distances: Vec<Distance> = vec![None; pairs.len()];
+            let mut stale_indices = Vec::new();
+            let mut stale_pairs = Vec::new();
+            let mut reused = 0usize;
+
+            for (idx, &(i, j)) in pairs.iter().enumerate() {
+                if i == j {
+                    distances[idx] = Some(0);
+                    continue;
+                }
+                if unchanged[i] && unchanged[j] {
+                    if let Some(&cached_distance) =
+                        prev_cache.get(&pair_key(&labels[i], &labels[j]))
+                    {
+                        distances[idx] = cached_distance;
+                        reused += 1;
+                        continue;
+                    }
+                }
+                stale_indices.push(idx);
+                stale_pairs.push((i, j));
+            }
+
+            let (stale_distances, log) = run_pairs_in_parallel(
+                data_map,
+                &labels,
+                &stale_pairs,
+                maxdist,
+                input_format,
+                compute_options.threads,
+            );
+            for (idx, distance) in stale_indices.into_iter().zip(stale_distances) {
+                distances[idx] = distance;
+            }
+
+            if let Some(path) = compute_options.joblog {
+                write_joblog(path, &log)?;
+            }
+
+            info!(
+                "Update mode: reused {} of {} pairwise distances from {}",
+                reused,
+                pairs.len(),
+                prev_matrix_path.display()
+            );
+
+            DistanceResult {
+                labels,
+                mode,
+                distances,
+            }
+        }
+        None => {
+            info!(
+                "No usable manifest/previous matrix at {} / {}, computing from scratch",
+                prev_matrix_path.display(),
+                manifest_path.display()
+            );
+            compute_distances(
+                data_map,
+                maxdist,
+                mode,
+                input_format,
+                compute_options.threads,
+                compute_options.joblog,
+            )?
+        }
+    };
+
+    write_manifest(manifest_path, &current_hashes, maxdist)?;
+    Ok(result)
+}
+
+/// Fast content hash of a sample's normalized profile, used to detect unchanged samples across
+/// runs without comparing full profiles.
+fn hash_profile(profile: &[String]) -> String {
+    let mut hasher = DefaultHasher::new();
+    profile.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+fn pair_key(a: &str, b: &str) -> (String, String) {
+    if a <= b {
+        (a.to_string(), b.to_string())
+    } else {
+        (b.to_string(), a.to_string())
+    }
+}
+
+/// Reads the sidecar manifest written by a previous run, returning `None` if it's missing, was
+/// written by an incompatible hash algorithm version, or its header doesn't parse.
+fn read_manifest(path: &Path) -> Result<Option<Manifest>, Box<dyn Error>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => return Ok(None),
+    };
+
+    let mut lines = content.lines();
+    let maxdist = match lines.next().and_then(|header| header.split_once('\t')) {
+        Some((version, maxdist_field)) if version == format!("#{}", MANIFEST_VERSION) => {
+            match parse_maxdist(maxdist_field) {
+                Some(maxdist) => maxdist,
+                None => return Ok(None),
+            }
+        }
+        _ => return Ok(None),
+    };
+
+    let mut hashes = HashMap::new();
+    for line in lines {
+        if let Some((label, hash)) = line.split_once('\t') {
+            hashes.insert(label.to_string(), hash.to_string());
+        }
+    }
+    Ok(Some(Manifest { hashes, maxdist }))
+}
+
+fn write_manifest(
+    path: &Path,
+    hashes: &HashMap<String, String>,
+    maxdist: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    let mut labels: Vec<&String> = hashes.keys().collect();
+    labels.sort();
+
+    let mut out = format!(
+        "#{}\tmaxdist={}\n",
+        MANIFEST_VERSION,
+        format_maxdist(maxdist)
+    );
+    for label in labels {
+        out.push_str(label);
+        out.push('\t');
+        out.push_str(&hashes[label]);
+        out.push('\n');
+    }
+
+    fs::write(path, out)?;
+    Ok(())
+}
+
+fn format_maxdist(maxdist: Option<usize>) -> String {
+    match maxdist {
+        Some(m) => m.to_string(),
+        None => "none".to_string(),
+    }
+}
+
+/// Parses the `maxdist=<value>` header field. The outer `Option` is `None` if the field doesn't
+/// parse at all (stale/corrupt manifest); the inner one is the `--maxdist` value itself, where
+/// `"none"` means the run had no cap.
+fn parse_maxdist(field: &str) -> Option<Option<usize>> {
+    let value = field.strip_prefix("maxdist=")?;
+    if value == "none" {
+        Some(None)
+    } else {
+        value.parse::<usize>().ok().map(Some)
+    }
+}
+
+/// Parses a previously written distance matrix into a lookup keyed by unordered label pair.
+///
+/// Returns `None` if the file is missing or doesn't parse as a matrix produced by this mode
+/// (e.g. a stale matrix from a differing `--output-mode`), so the caller can fall back safely.
+fn load_previous_matrix(
+    path: &Path,
+    sep: char,
+    mode: OutputMode,
+) -> Result<Option<PairDistanceCache>, Box<dyn Error>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => return Ok(None),
+    };
+
+    let mut lines = content.lines();
+    let labels: Vec<String> = match lines.next() {
+        Some(header) => header.split(sep).map(|s| s.to_string()).collect(),
+        None => return Ok(None),
+    };
+
+    let mut cache = HashMap::new();
+    for (i, line) in lines.enumerate() {
+        let mut fields = line.split(sep);
+        let row_label = match fields.next() {
+            Some(label) => label.to_string(),
+            None => return Ok(None),
+        };
+        if labels.get(i) != Some(&row_label) {
+            return Ok(None);
+        }
+
+        for (j, cell) in fields.enumerate() {
+            let col_label = match mode {
+                OutputMode::LowerTriangle if j < i => &labels[j],
+                OutputMode::Full if j < labels.len() => &labels[j],
+                _ => return Ok(None),
+            };
+            let distance = if cell.is_empty() {
+                None
+            } else {
+                match cell.parse::<usize>() {
+                    Ok(d) => Some(d),
+                    Err(_) => return Ok(None),
+                }
+            };
+            cache.insert(pair_key(&row_label, col_label), distance);
+        }
+    }
+
+    Ok(Some(cache))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    fn data_map(samples: &[(&str, &str)]) -> DataMap {
+        samples
+            .iter()
+            .map(|(label, seq)| {
+                (
+                    label.to_string(),
+                    seq.chars().map(|c| c.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn scratch_paths(name: &str) -> (std::path::PathBuf, std::path::PathBuf) {
+        let dir = std::env::temp_dir();
+        let matrix = dir.join(format!("distle-test-{}-{}.tsv", name, std::process::id()));
+        let manifest = dir.join(format!(
+            "distle-test-{}-{}.tsv.manifest",
+            name,
+            std::process::id()
+        ));
+        (matrix, manifest)
+    }
+
+    #[test]
+    fn maxdist_mismatch_forces_full_recompute_instead_of_reusing_stale_values() {
+        let (matrix_path, manifest_path) = scratch_paths("maxdist-mismatch");
+
+        let samples = data_map(&[("s1", "AAAA"), ("s2", "AAAT"), ("s3", "AATT"), ("s4", "TTTT")]);
+
+        let first = compute_distances_incremental(
+            &samples,
+            None,
+            OutputMode::LowerTriangle,
+            InputFormat::Fasta,
+            &matrix_path,
+            &manifest_path,
+            '\t',
+            ComputeOptions {
+                threads: None,
+                joblog: None,
+            },
+        )
+        .unwrap();
+        let mut matrix_file = File::create(&matrix_path).unwrap();
+        crate::processing::write_tabular(first, &mut matrix_file, '\t').unwrap();
+
+        // Same (unchanged) samples, but a tighter maxdist than the cached matrix was computed
+        // with: reusing the cached values verbatim would wrongly keep s1xs4 and s2xs4 uncapped.
+        let second = compute_distances_incremental(
+            &samples,
+            Some(2),
+            OutputMode::LowerTriangle,
+            InputFormat::Fasta,
+            &matrix_path,
+            &manifest_path,
+            '\t',
+            ComputeOptions {
+                threads: None,
+                joblog: None,
+            },
+        )
+        .unwrap();
+
+        let index_of = |label: &str| second.labels.iter().position(|l| l == label).unwrap();
+        let distance_of = |a: &str, b: &str| {
+            let (hi, lo) = {
+                let (ia, ib) = (index_of(a), index_of(b));
+                if ia > ib { (ia, ib) } else { (ib, ia) }
+            };
+            second.distances[hi * (hi - 1) / 2 + lo]
+        };
+
+        assert_eq!(distance_of("s1", "s4"), None);
+        assert_eq!(distance_of("s2", "s4"), None);
+        assert_eq!(distance_of("s1", "s2"), Some(1));
+
+        let _ = fs::remove_file(&matrix_path);
+        let _ = fs::remove_file(&manifest_path);
+    }
+
+    #[test]
+    fn unchanged_samples_reuse_cached_distances_instead_of_recomputing() {
+        let (matrix_path, manifest_path) = scratch_paths("cache-reuse");
+
+        let samples = data_map(&[("s1", "AAAA"), ("s2", "AAAT"), ("s3", "TTTT")]);
+
+        let first = compute_distances_incremental(
+            &samples,
+            None,
+            OutputMode::LowerTriangle,
+            InputFormat::Fasta,
+            &matrix_path,
+            &manifest_path,
+            '\t',
+            ComputeOptions {
+                threads: None,
+                joblog: None,
+            },
+        )
+        .unwrap();
+        let mut matrix_file = File::create(&matrix_path).unwrap();
+        crate::processing::write_tabular(first, &mut matrix_file, '\t').unwrap();
+
+        // Tamper with the written matrix so the cached s1xs2 distance (really 1) no longer
+        // matches what a recompute would produce. None of the samples change, so a second run
+        // should reuse this tampered value verbatim rather than silently recomputing the real
+        // one — if it surfaces, we know the cache-reuse path (not just the fallback) actually ran.
+        let tampered = fs::read_to_string(&matrix_path)
+            .unwrap()
+            .replace("s2\t1\n", "s2\t99\n");
+        fs::write(&matrix_path, tampered).unwrap();
+
+        let second = compute_distances_incremental(
+            &samples,
+            None,
+            OutputMode::LowerTriangle,
+            InputFormat::Fasta,
+            &matrix_path,
+            &manifest_path,
+            '\t',
+            ComputeOptions {
+                threads: None,
+                joblog: None,
+            },
+        )
+        .unwrap();
+
+        let index_of = |label: &str| second.labels.iter().position(|l| l == label).unwrap();
+        let distance_of = |a: &str, b: &str| {
+            let (hi, lo) = {
+                let (ia, ib) = (index_of(a), index_of(b));
+                if ia > ib { (ia, ib) } else { (ib, ia) }
+            };
+            second.distances[hi * (hi - 1) / 2 + lo]
+        };
+
+        assert_eq!(distance_of("s1", "s2"), Some(99));
+        // A pair not tampered with still reflects its real (recomputable) distance, confirming
+        // the tampered value above came from the cache rather than both being stale leftovers.
+        assert_eq!(distance_of("s1", "s3"), Some(4));
+
+        let _ = fs::remove_file(&matrix_path);
+        let _ = fs::remove_file(&manifest_path);
+    }
+}
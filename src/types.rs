@@ -0,0 +1,12 @@
+use clap::ValueEnum;
+
+/// Supported formats for the input dataset
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputFormat {
+    /// FASTA alignment, comparing only core (shared, non-gap/non-N) positions
+    Fasta,
+    /// FASTA alignment, comparing every position regardless of gaps/Ns
+    FastaAll,
+    /// Tabular allele profile (e.g. cgMLST/wgMLST calls), one row per sample
+    Tabular,
+}